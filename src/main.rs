@@ -1,11 +1,16 @@
 use std::collections::HashMap;
 use std::io::{Result, Write};
 
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
 use colored::*;
 use nom::branch::alt;
-use nom::bytes::streaming::{tag, take, take_while1};
-use nom::combinator::map;
-use nom::sequence::{pair, preceded, separated_pair};
+use nom::bytes::complete as cbytes;
+use nom::bytes::streaming::{tag, take, take_until, take_while1};
+use nom::character::complete as cchar;
+use nom::character::streaming::digit1;
+use nom::combinator::{all_consuming, map, map_res, opt};
+use nom::sequence::{delimited, pair, preceded, separated_pair};
 use nom::IResult;
 use oops::Oops;
 use stdinix::stdinix;
@@ -14,15 +19,38 @@ use structopt::StructOpt;
 #[derive(StructOpt, Debug)]
 #[structopt(name = "basic")]
 struct Opt {
-    /// Either "hex" or "approximation".
+    /// One of "hex", "approximation", or "canonical" (a hexdump -C style dump).
     #[structopt(short = "r", long = "representation", default_value = "approximation")]
     repr: String,
+
+    /// Reassemble each TCP connection's data into a single stream per direction and print
+    /// it at EOF, instead of echoing tcpdump's output line by line.
+    #[structopt(long = "follow")]
+    follow: bool,
+
+    /// A BPF-like display filter, e.g. "host 192.168.0.10 and port 8008" or
+    /// "src 10.0.0.1 or dst 10.0.0.2". Only matching connections are printed.
+    #[structopt(long = "filter")]
+    filter: Option<String>,
+
+    /// Decrypt each --follow connection's reconstructed payload before rendering it.
+    /// Currently only "chacha20" is supported, and requires --key and --nonce.
+    #[structopt(long = "decrypt")]
+    decrypt: Option<String>,
+
+    /// 32-byte ChaCha20 key, as 64 hex characters.
+    #[structopt(long = "key")]
+    key: Option<String>,
+
+    /// 12-byte ChaCha20 nonce, as 24 hex characters.
+    #[structopt(long = "nonce")]
+    nonce: Option<String>,
 }
 
 #[derive(Debug, PartialEq)]
 enum TcpdumpLine<'a> {
     Ip(&'a [u8], &'a [u8]),
-    Tcp(HostPort<'a>, HostPort<'a>, &'a [u8]),
+    Tcp(HostPort<'a>, HostPort<'a>, TcpRepr<'a>),
     Data(&'a [u8], &'a [u8]),
 }
 
@@ -55,6 +83,14 @@ fn parse_ip_line(input: &[u8]) -> IResult<&[u8], TcpdumpLine> {
     )(input)
 }
 
+// 00:55:30.875722 IP6 fe80::1.443 > 2001:db8::2.80: Flags ...
+fn parse_ip6_line(input: &[u8]) -> IResult<&[u8], TcpdumpLine> {
+    map(
+        separated_pair(parse_timestamp, tag(" IP6 "), frame_info),
+        |(a, b)| TcpdumpLine::Ip(a, b),
+    )(input)
+}
+
 #[derive(Debug, PartialEq)]
 struct HostPort<'a> {
     host: &'a [u8],
@@ -74,18 +110,109 @@ fn tcp_source(input: &[u8]) -> IResult<&[u8], HostPort> {
     map(preceded(tag("    "), not_whitespace), parse_host_port)(input)
 }
 
-// " > 192.168.0.20.50314"
+// " > 192.168.0.20.50314" or " > 2001:db8::2.80" (IPv6 addresses are colon-heavy,
+// so stop at the literal ": " that introduces tcp_repr rather than the first colon)
 fn tcp_dest(input: &[u8]) -> IResult<&[u8], HostPort> {
-    map(preceded(tag(" > "), not_colon), parse_host_port)(input)
+    map(preceded(tag(" > "), take_until(": ")), parse_host_port)(input)
+}
+
+// [S.], [S], [.], [F.], [R], ...
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct TcpFlags(u8);
+
+impl TcpFlags {
+    const SYN: u8 = 0b0000_0001;
+    const ACK: u8 = 0b0000_0010;
+    const FIN: u8 = 0b0000_0100;
+    const RST: u8 = 0b0000_1000;
+    const PSH: u8 = 0b0001_0000;
+    const URG: u8 = 0b0010_0000;
+
+    fn from_chars(chars: &[u8]) -> TcpFlags {
+        TcpFlags(chars.iter().fold(0u8, |bits, byte| {
+            bits | match byte {
+                b'S' => TcpFlags::SYN,
+                b'.' => TcpFlags::ACK,
+                b'F' => TcpFlags::FIN,
+                b'R' => TcpFlags::RST,
+                b'P' => TcpFlags::PSH,
+                b'U' => TcpFlags::URG,
+                _ => 0,
+            }
+        }))
+    }
+
+    fn contains(self, flag: u8) -> bool {
+        self.0 & flag != 0
+    }
+}
+
+// The structured parts of ": Flags [.], cksum 0x0e2e (correct), seq 4278946470, ack
+// 3104177948, win 508, options [nop,nop,TS val 3361824424...]", as parsed by `tcp_repr`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TcpRepr<'a> {
+    flags: TcpFlags,
+    // Absent for pure ACKs that carry no data, e.g. "Flags [.], ack 4126802986, win 4620".
+    seq: Option<u32>,
+    ack: Option<u32>,
+    win: u32,
+    options: Option<&'a [u8]>,
+}
+
+fn tcp_flags(input: &[u8]) -> IResult<&[u8], TcpFlags> {
+    map(
+        delimited(tag("Flags ["), take_while1(|byte: u8| byte != b']'), tag("]")),
+        TcpFlags::from_chars,
+    )(input)
+}
+
+fn parse_u32(input: &[u8]) -> IResult<&[u8], u32> {
+    map_res(digit1, |digits: &[u8]| {
+        std::str::from_utf8(digits).unwrap().parse::<u32>()
+    })(input)
+}
+
+// A data-carrying segment prints "seq <first>:<last>" (and occasionally "ack <first>:<last>")
+// rather than a bare number; take the first number of the range either way.
+fn parse_u32_range(input: &[u8]) -> IResult<&[u8], u32> {
+    let (input, first) = parse_u32(input)?;
+    let (input, _) = opt(preceded(tag(":"), parse_u32))(input)?;
+    Ok((input, first))
 }
 
 // : Flags [.], cksum 0x0e2e (correct), seq 4278946470, ack 3104177948, win 508, options [nop,nop,TS val 3361824424...
-fn tcp_info(input: &[u8]) -> IResult<&[u8], &[u8]> {
-    not_linebreak(input)
+// : Flags [P.], seq 1:21, ack 1, win 92, length 20
+// : Flags [.], ack 4126802986, win 4620, length 0   (pure ACK, no data: "seq" is omitted entirely)
+fn tcp_repr(input: &[u8]) -> IResult<&[u8], TcpRepr> {
+    let (input, _) = tag(": ")(input)?;
+    let (input, flags) = tcp_flags(input)?;
+    // Skip any preceding fields (e.g. ", cksum 0x0e2e (correct)") up to whichever of
+    // "seq "/"ack " actually opens the seq/ack portion of the line. These use the
+    // `complete` take_until (rather than the `streaming` one used elsewhere in this
+    // parser) so that a missing "seq " falls through to the "ack " alternative as a
+    // normal Error instead of an unresolvable Incomplete.
+    let (input, _) = alt((cbytes::take_until("seq "), cbytes::take_until("ack ")))(input)?;
+    let (input, seq) = opt(preceded(tag("seq "), parse_u32_range))(input)?;
+    let ack_tag = if seq.is_some() { ", ack " } else { "ack " };
+    let (input, ack) = opt(preceded(tag(ack_tag), parse_u32_range))(input)?;
+    let (input, _) = tag(", win ")(input)?;
+    let (input, win) = parse_u32(input)?;
+    let (input, options) = opt(preceded(tag(", options ["), take_until("]")))(input)?;
+
+    Ok((
+        input,
+        TcpRepr {
+            flags,
+            seq,
+            ack,
+            win,
+            options,
+        },
+    ))
 }
 
 fn parse_tcp_line(input: &[u8]) -> IResult<&[u8], TcpdumpLine> {
-    map(pair(pair(tcp_source, tcp_dest), tcp_info), |((a, b), c)| {
+    map(pair(pair(tcp_source, tcp_dest), tcp_repr), |((a, b), c)| {
         TcpdumpLine::Tcp(a, b, c)
     })(input)
 }
@@ -113,7 +240,7 @@ fn parse_data_line(input: &[u8]) -> IResult<&[u8], TcpdumpLine> {
 }
 
 fn tcpdump_parser(input: &[u8]) -> IResult<&[u8], TcpdumpLine> {
-    alt((parse_ip_line, parse_tcp_line, parse_data_line))(input)
+    alt((parse_ip_line, parse_ip6_line, parse_tcp_line, parse_data_line))(input)
 }
 
 fn colored_string<'a>(text: &'a [u8], map: &mut HashMap<String, ColoredString>) -> ColoredString {
@@ -138,40 +265,429 @@ fn colored_string<'a>(text: &'a [u8], map: &mut HashMap<String, ColoredString>)
         .to_owned()
 }
 
-fn write_repr(approximation: &String, hex: &String, config: &Opt) {
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct OwnedHostPort {
+    host: Vec<u8>,
+    port: Vec<u8>,
+}
+
+impl<'a> From<&HostPort<'a>> for OwnedHostPort {
+    fn from(hp: &HostPort<'a>) -> Self {
+        OwnedHostPort {
+            host: hp.host.to_vec(),
+            port: hp.port.to_vec(),
+        }
+    }
+}
+
+type ConnKey = (OwnedHostPort, OwnedHostPort);
+
+// Canonicalize a pair of endpoints so both directions of a connection hash to the same key.
+fn conn_key(source: &HostPort, dest: &HostPort) -> ConnKey {
+    let source = OwnedHostPort::from(source);
+    let dest = OwnedHostPort::from(dest);
+    if source <= dest {
+        (source, dest)
+    } else {
+        (dest, source)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Connection {
+    // (seq, bytes) chunks seen travelling from key.0 to key.1, in arrival order.
+    forward: Vec<(u32, Vec<u8>)>,
+    // (seq, bytes) chunks seen travelling from key.1 to key.0, in arrival order.
+    backward: Vec<(u32, Vec<u8>)>,
+}
+
+// Sort a connection's chunks by TCP seq number (stable, so lines within the same segment
+// keep their arrival order) and concatenate them into the reassembled stream.
+fn reassemble(chunks: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut chunks = chunks.to_vec();
+    chunks.sort_by_key(|(seq, _)| *seq);
+    chunks.into_iter().flat_map(|(_, bytes)| bytes).collect()
+}
+
+#[derive(Debug, Default)]
+struct Follower {
+    connections: HashMap<ConnKey, Connection>,
+    current: Option<(ConnKey, bool, u32)>,
+}
+
+// Decode tcpdump's space-separated hex groups (e.g. "4500 0233 b512 ...") back into bytes.
+fn decode_hex_groups(hx: &[u8]) -> Vec<u8> {
+    hx.iter()
+        .cloned()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .collect::<Vec<u8>>()
+        .chunks(2)
+        .filter_map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+        })
+        .collect()
+}
+
+// Strictly decode a hex string into bytes, rejecting the whole input on the first
+// invalid byte pair rather than silently dropping it (unlike the lenient
+// `decode_hex_groups`, which is only meant for tcpdump's own hex dump output).
+fn decode_hex_strict(hex: &[u8]) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    hex.chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+        })
+        .collect()
+}
+
+// Parse a hex-encoded ChaCha20 key/nonce CLI argument into a fixed-size array.
+fn parse_hex_array<T: std::convert::TryFrom<Vec<u8>>>(hex: &str) -> Option<T> {
+    use std::convert::TryInto;
+
+    decode_hex_strict(hex.as_bytes())?.try_into().ok()
+}
+
+// Decrypt a copy of a reassembled connection payload with the given ChaCha20 key/nonce.
+fn chacha20_decrypt(bytes: &[u8], key: [u8; 32], nonce: [u8; 12]) -> Vec<u8> {
+    let mut buffer = bytes.to_vec();
+    let mut cipher = ChaCha20::new(&key.into(), &nonce.into());
+    cipher.apply_keystream(&mut buffer);
+    buffer
+}
+
+fn follow_out(follower: &mut Follower, parsed: &TcpdumpLine, filter: Option<&FilterExpr>) {
+    match parsed {
+        TcpdumpLine::Tcp(source, dest, repr) => {
+            let matches = match filter {
+                Some(expr) => filter_matches(expr, source, dest),
+                None => true,
+            };
+            if !matches {
+                follower.current = None;
+                return;
+            }
+
+            let key = conn_key(source, dest);
+            let is_forward = OwnedHostPort::from(source) == key.0;
+            follower.connections.entry(key.clone()).or_default();
+            // A pure ACK carries no seq; its Data lines (the packet's header bytes) are
+            // recorded under seq 0 and so sort ahead of the seq-numbered chunks.
+            follower.current = Some((key, is_forward, repr.seq.unwrap_or(0)));
+        }
+        TcpdumpLine::Data(hx, _) => {
+            if let Some((key, is_forward, seq)) = &follower.current {
+                if let Some(connection) = follower.connections.get_mut(key) {
+                    let bytes = decode_hex_groups(hx);
+                    if *is_forward {
+                        connection.forward.push((*seq, bytes));
+                    } else {
+                        connection.backward.push((*seq, bytes));
+                    }
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+fn format_payload(bytes: &[u8], config: &Opt) -> String {
+    match &config.repr[..] {
+        "hex" => bytes
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<String>>()
+            .join(" "),
+        "canonical" => canonical_hexdump(bytes),
+        _ => bytes
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect(),
+    }
+}
+
+fn flush_follower(
+    follower: &Follower,
+    colors: &mut HashMap<String, ColoredString>,
+    cipher: Option<([u8; 32], [u8; 12])>,
+    config: &Opt,
+) {
+    let decrypt = |bytes: Vec<u8>| match cipher {
+        Some((key, nonce)) => chacha20_decrypt(&bytes, key, nonce),
+        None => bytes,
+    };
+
+    for (key, connection) in &follower.connections {
+        println!(
+            "\n{} -> {}",
+            colored_string(&key.0.host, colors),
+            colored_string(&key.1.host, colors)
+        );
+        println!(
+            "{}",
+            format_payload(&decrypt(reassemble(&connection.forward)), config)
+        );
+
+        println!(
+            "\n{} -> {}",
+            colored_string(&key.1.host, colors),
+            colored_string(&key.0.host, colors)
+        );
+        println!(
+            "{}",
+            format_payload(&decrypt(reassemble(&connection.backward)), config)
+        );
+    }
+}
+
+// A BPF-like display filter AST, e.g. "host 192.168.0.10 and port 8008" or
+// "src 10.0.0.1 or dst 10.0.0.2".
+#[derive(Debug, Clone, PartialEq)]
+enum FilterExpr {
+    Host(Vec<u8>),
+    Port(Vec<u8>),
+    Src(Box<FilterExpr>),
+    Dst(Box<FilterExpr>),
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+    Not(Box<FilterExpr>),
+}
+
+fn filter_atom(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    cbytes::take_while1(|byte: u8| byte.is_ascii_alphanumeric() || byte == b'.' || byte == b':')(input)
+}
+
+fn filter_host(input: &[u8]) -> IResult<&[u8], FilterExpr> {
+    map(
+        preceded(pair(cbytes::tag("host"), cchar::multispace1), filter_atom),
+        |addr: &[u8]| FilterExpr::Host(addr.to_vec()),
+    )(input)
+}
+
+fn filter_port(input: &[u8]) -> IResult<&[u8], FilterExpr> {
+    map(
+        preceded(pair(cbytes::tag("port"), cchar::multispace1), cchar::digit1),
+        |port: &[u8]| FilterExpr::Port(port.to_vec()),
+    )(input)
+}
+
+// A bare address with no "host"/"port" keyword, as in the `src 10.0.0.1` shorthand for
+// `src host 10.0.0.1`.
+fn filter_bare_host(input: &[u8]) -> IResult<&[u8], FilterExpr> {
+    map(filter_atom, |addr: &[u8]| FilterExpr::Host(addr.to_vec()))(input)
+}
+
+// What a `src`/`dst` qualifier binds to: an explicit `host`/`port` atom, or a bare address.
+fn filter_qualified_target(input: &[u8]) -> IResult<&[u8], FilterExpr> {
+    alt((filter_host, filter_port, filter_bare_host))(input)
+}
+
+fn filter_src(input: &[u8]) -> IResult<&[u8], FilterExpr> {
+    map(
+        preceded(pair(cbytes::tag("src"), cchar::multispace1), filter_qualified_target),
+        |inner| FilterExpr::Src(Box::new(inner)),
+    )(input)
+}
+
+fn filter_dst(input: &[u8]) -> IResult<&[u8], FilterExpr> {
+    map(
+        preceded(pair(cbytes::tag("dst"), cchar::multispace1), filter_qualified_target),
+        |inner| FilterExpr::Dst(Box::new(inner)),
+    )(input)
+}
+
+fn filter_not(input: &[u8]) -> IResult<&[u8], FilterExpr> {
+    map(
+        preceded(pair(cbytes::tag("not"), cchar::multispace1), filter_term),
+        |inner| FilterExpr::Not(Box::new(inner)),
+    )(input)
+}
+
+fn filter_paren(input: &[u8]) -> IResult<&[u8], FilterExpr> {
+    delimited(
+        pair(cchar::char('('), cchar::multispace0),
+        filter_or,
+        pair(cchar::multispace0, cchar::char(')')),
+    )(input)
+}
+
+fn filter_term(input: &[u8]) -> IResult<&[u8], FilterExpr> {
+    alt((filter_src, filter_dst, filter_not, filter_host, filter_port, filter_paren))(input)
+}
+
+fn filter_and(input: &[u8]) -> IResult<&[u8], FilterExpr> {
+    let (mut input, mut expr) = filter_term(input)?;
+    while let Ok((rest, next)) = preceded(
+        delimited(cchar::multispace1, cbytes::tag("and"), cchar::multispace1),
+        filter_term,
+    )(input)
+    {
+        expr = FilterExpr::And(Box::new(expr), Box::new(next));
+        input = rest;
+    }
+    Ok((input, expr))
+}
+
+fn filter_or(input: &[u8]) -> IResult<&[u8], FilterExpr> {
+    let (mut input, mut expr) = filter_and(input)?;
+    while let Ok((rest, next)) = preceded(
+        delimited(cchar::multispace1, cbytes::tag("or"), cchar::multispace1),
+        filter_and,
+    )(input)
+    {
+        expr = FilterExpr::Or(Box::new(expr), Box::new(next));
+        input = rest;
+    }
+    Ok((input, expr))
+}
+
+// Reject a filter expression with unparsed trailing input (e.g. a typo'd combinator
+// like "host 192.168.1.1 amd port 9999") instead of silently matching only the prefix.
+fn parse_filter(input: &[u8]) -> IResult<&[u8], FilterExpr> {
+    all_consuming(delimited(cchar::multispace0, filter_or, cchar::multispace0))(input)
+}
+
+// Does `expr` match this connection's source/dest pair, considered as a whole?
+fn filter_matches(expr: &FilterExpr, source: &HostPort, dest: &HostPort) -> bool {
+    match expr {
+        FilterExpr::Host(addr) => source.host == addr.as_slice() || dest.host == addr.as_slice(),
+        FilterExpr::Port(port) => source.port == port.as_slice() || dest.port == port.as_slice(),
+        FilterExpr::Src(inner) => filter_matches_endpoint(inner, source),
+        FilterExpr::Dst(inner) => filter_matches_endpoint(inner, dest),
+        FilterExpr::And(a, b) => filter_matches(a, source, dest) && filter_matches(b, source, dest),
+        FilterExpr::Or(a, b) => filter_matches(a, source, dest) || filter_matches(b, source, dest),
+        FilterExpr::Not(inner) => !filter_matches(inner, source, dest),
+    }
+}
+
+// Does `expr` match a single endpoint, e.g. the inner term of a `src`/`dst` qualifier?
+fn filter_matches_endpoint(expr: &FilterExpr, endpoint: &HostPort) -> bool {
+    match expr {
+        FilterExpr::Host(addr) => endpoint.host == addr.as_slice(),
+        FilterExpr::Port(port) => endpoint.port == port.as_slice(),
+        FilterExpr::Src(inner) | FilterExpr::Dst(inner) => filter_matches_endpoint(inner, endpoint),
+        FilterExpr::And(a, b) => {
+            filter_matches_endpoint(a, endpoint) && filter_matches_endpoint(b, endpoint)
+        }
+        FilterExpr::Or(a, b) => {
+            filter_matches_endpoint(a, endpoint) || filter_matches_endpoint(b, endpoint)
+        }
+        FilterExpr::Not(inner) => !filter_matches_endpoint(inner, endpoint),
+    }
+}
+
+// hexdump -C style: an 8-digit offset, two 8-byte hex columns, and a |...| ASCII gutter.
+fn canonical_hexdump(bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let mut line = format!("{:08x}  ", row * 16);
+            for column in 0..16 {
+                match chunk.get(column) {
+                    Some(byte) => line.push_str(&format!("{:02x} ", byte)),
+                    None => line.push_str("   "),
+                }
+                if column == 7 {
+                    line.push(' ');
+                }
+            }
+            line.push('|');
+            for &byte in chunk {
+                line.push(if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                });
+            }
+            line.push('|');
+            line
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn write_repr(approximation: &String, hex: &String, raw: &[u8], config: &Opt) {
     match &config.repr[..] {
         "approximation" => println!("{}", approximation),
         "hex" => println!("{}", hex),
+        "canonical" => println!("{}", canonical_hexdump(raw)),
         _ => eprintln!("Data ignored"),
     }
 }
 
-fn write_out<'a, 'b>(
-    hex: &'a mut String,
-    approximation: &'a mut String,
-    parsed: &'b TcpdumpLine,
-    colors: &'a mut HashMap<String, ColoredString>,
-    config: &Opt,
-) -> () {
-    match (hex.len(), parsed) {
+// Mutable rendering state threaded through write_out across stdin lines: the
+// in-progress hex/approximation/raw blob for the current packet, whether it
+// matched the active --filter, and the per-host color assignments.
+struct RenderState {
+    hex: String,
+    approximation: String,
+    raw: Vec<u8>,
+    matched: bool,
+    colors: HashMap<String, ColoredString>,
+}
+
+impl Default for RenderState {
+    fn default() -> Self {
+        RenderState {
+            hex: String::new(),
+            approximation: String::new(),
+            raw: Vec::new(),
+            matched: true,
+            colors: HashMap::new(),
+        }
+    }
+}
+
+fn write_out(state: &mut RenderState, parsed: &TcpdumpLine, filter: Option<&FilterExpr>, config: &Opt) {
+    match (state.hex.len(), parsed) {
         (_, TcpdumpLine::Data(hx, apprx)) => {
-            hex.extend(vec![' '].into_iter());
-            hex.extend(hx.into_iter().cloned().map(char::from));
-            approximation.extend(apprx.into_iter().cloned().map(char::from));
+            state.hex.extend(vec![' '].into_iter());
+            state.hex.extend(hx.into_iter().cloned().map(char::from));
+            state
+                .approximation
+                .extend(apprx.into_iter().cloned().map(char::from));
+            state.raw.extend(decode_hex_groups(hx));
         }
         (len, _) if len > 0 => {
-            write_repr(approximation, hex, config);
-            approximation.clear();
-            hex.clear();
+            if state.matched {
+                write_repr(&state.approximation, &state.hex, &state.raw, config);
+            }
+            state.approximation.clear();
+            state.hex.clear();
+            state.raw.clear();
 
-            write_out(hex, approximation, parsed, colors, config);
+            write_out(state, parsed, filter, config);
         }
-        (_, TcpdumpLine::Tcp(source, dest, _)) => {
-            println!(
-                "\n{} -> {}",
-                colored_string(source.host, colors),
-                colored_string(dest.host, colors)
+        (_, TcpdumpLine::Tcp(source, dest, repr)) => {
+            state.matched = filter.is_none_or(|expr| filter_matches(expr, source, dest));
+            if !state.matched {
+                return;
+            }
+
+            let header = format!(
+                "{} -> {}",
+                colored_string(source.host, &mut state.colors),
+                colored_string(dest.host, &mut state.colors)
             );
+            if repr.flags.contains(TcpFlags::SYN) {
+                println!("\n{}", header.green());
+            } else if repr.flags.contains(TcpFlags::RST) {
+                println!("\n{}", header.red());
+            } else {
+                println!("\n{}", header);
+            }
         }
         _ => (),
     }
@@ -179,9 +695,43 @@ fn write_out<'a, 'b>(
 
 fn main() -> Result<()> {
     let options = Opt::from_args();
-    let mut hex = String::new();
-    let mut approximation = String::new();
-    let mut colors = HashMap::new();
+    let filter = options
+        .filter
+        .as_ref()
+        .map(|expr| {
+            parse_filter(expr.as_bytes())
+                .map_err(|e| eprintln!("{:?}", e))
+                .oops("Failed to parse filter")
+        })
+        .transpose()?
+        .map(|(_, expr)| expr);
+    let cipher = match options.decrypt.as_deref() {
+        Some("chacha20") => match (&options.key, &options.nonce) {
+            (Some(key), Some(nonce)) => {
+                match (parse_hex_array(key), parse_hex_array(nonce)) {
+                    (Some(key), Some(nonce)) => Some((key, nonce)),
+                    _ => {
+                        eprintln!("--key must be 64 hex characters and --nonce must be 24 hex characters");
+                        None
+                    }
+                }
+            }
+            _ => {
+                eprintln!("--decrypt chacha20 requires --key and --nonce");
+                None
+            }
+        },
+        Some(other) => {
+            eprintln!("Unsupported --decrypt transform: {}", other);
+            None
+        }
+        None => None,
+    };
+    if cipher.is_some() && !options.follow {
+        eprintln!("--decrypt only applies to --follow output; ignoring it here");
+    }
+    let mut state = RenderState::default();
+    let mut follower = Follower::default();
     stdinix(|line| {
         std::io::stdout().flush()?;
         let parsed = tcpdump_parser(line.as_bytes())
@@ -189,14 +739,21 @@ fn main() -> Result<()> {
             .oops("Failed to parse")?
             .1;
 
-        write_out(&mut hex, &mut approximation, &parsed, &mut colors, &options);
-        std::io::stdout().flush()?;
+        if options.follow {
+            follow_out(&mut follower, &parsed, filter.as_ref());
+        } else {
+            write_out(&mut state, &parsed, filter.as_ref(), &options);
+            std::io::stdout().flush()?;
+        }
 
         Ok(())
     })?;
 
-    if !hex.is_empty() {
-        write_repr(&approximation, &hex, &options);
+    if options.follow {
+        flush_follower(&follower, &mut state.colors, cipher, &options);
+        std::io::stdout().flush()?;
+    } else if !state.hex.is_empty() && state.matched {
+        write_repr(&state.approximation, &state.hex, &state.raw, &options);
         std::io::stdout().flush()?;
     }
 
@@ -237,4 +794,270 @@ mod test {
             TcpdumpLine::Ip("00:55:30.853902".as_bytes(), "(tos 0x0, ttl 63, id 60304, offset 0, flags [DF], proto TCP (6), length 60)".as_bytes())
         );
     }
+
+    #[test]
+    fn test_ip6_line() {
+        assert_eq!(
+            parse_ip6_line("00:55:30.853902 IP6 (hlim 64, next-header TCP (6) payload length: 32)
+".as_bytes()).unwrap().1,
+            TcpdumpLine::Ip("00:55:30.853902".as_bytes(), "(hlim 64, next-header TCP (6) payload length: 32)".as_bytes())
+        );
+    }
+
+    #[test]
+    fn test_tcp_dest_ipv6() {
+        assert_eq!(
+            tcp_dest(" > 2001:db8::2.80: Flags [.]".as_bytes()).unwrap().1,
+            HostPort {
+                host: "2001:db8::2".as_bytes(),
+                port: "80".as_bytes(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_tcp_repr() {
+        assert_eq!(
+            tcp_repr(
+                ": Flags [.], cksum 0x0e2e (correct), seq 4278946470, ack 3104177948, win 508, options [nop,nop,TS val 3361824424 ecr 123]"
+                    .as_bytes()
+            )
+            .unwrap()
+            .1,
+            TcpRepr {
+                flags: TcpFlags(TcpFlags::ACK),
+                seq: Some(4278946470),
+                ack: Some(3104177948),
+                win: 508,
+                options: Some("nop,nop,TS val 3361824424 ecr 123".as_bytes()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_tcp_repr_seq_ack_range() {
+        assert_eq!(
+            tcp_repr(": Flags [P.], seq 1:21, ack 1, win 92, length 20".as_bytes())
+                .unwrap()
+                .1,
+            TcpRepr {
+                flags: TcpFlags(TcpFlags::PSH | TcpFlags::ACK),
+                seq: Some(1),
+                ack: Some(1),
+                win: 92,
+                options: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_tcp_repr_pure_ack_has_no_seq() {
+        // The classic tcpdump(8) rlogin example: a pure ACK carrying no data omits
+        // "seq" entirely.
+        assert_eq!(
+            tcp_repr(": Flags [.], ack 4126802986, win 4620, length 0".as_bytes())
+                .unwrap()
+                .1,
+            TcpRepr {
+                flags: TcpFlags(TcpFlags::ACK),
+                seq: None,
+                ack: Some(4126802986),
+                win: 4620,
+                options: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_tcp_flags_syn() {
+        assert_eq!(
+            tcp_flags("Flags [S]".as_bytes()).unwrap().1,
+            TcpFlags(TcpFlags::SYN)
+        );
+    }
+
+    #[test]
+    fn test_decode_hex_groups() {
+        assert_eq!(
+            decode_hex_groups("4500 0233 b512 4000".as_bytes()),
+            vec![0x45, 0x00, 0x02, 0x33, 0xb5, 0x12, 0x40, 0x00]
+        );
+    }
+
+    #[test]
+    fn test_parse_hex_array() {
+        let key: Option<[u8; 32]> = parse_hex_array(&"42".repeat(32));
+        assert_eq!(key, Some([0x42; 32]));
+
+        let too_short: Option<[u8; 32]> = parse_hex_array("42");
+        assert_eq!(too_short, None);
+
+        // An invalid byte pair must reject the whole key rather than being silently
+        // dropped, which would shift the remaining pairs and produce a different key.
+        let invalid = format!("zz{}", "42".repeat(31));
+        let rejected: Option<[u8; 32]> = parse_hex_array(&invalid);
+        assert_eq!(rejected, None);
+    }
+
+    #[test]
+    fn test_chacha20_decrypt_round_trips() {
+        let key = [0x42; 32];
+        let nonce = [0x24; 12];
+        let plaintext = b"hello, tcpdrunk!".to_vec();
+
+        let ciphertext = chacha20_decrypt(&plaintext, key, nonce);
+        assert_ne!(ciphertext, plaintext);
+        assert_eq!(chacha20_decrypt(&ciphertext, key, nonce), plaintext);
+    }
+
+    #[test]
+    fn test_canonical_hexdump() {
+        let bytes: Vec<u8> = b"foo\n".to_vec();
+        assert_eq!(
+            canonical_hexdump(&bytes),
+            "00000000  66 6f 6f 0a                                      |foo.|"
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_and_or() {
+        assert_eq!(
+            parse_filter("host 192.168.0.10 and port 8008".as_bytes())
+                .unwrap()
+                .1,
+            FilterExpr::And(
+                Box::new(FilterExpr::Host("192.168.0.10".as_bytes().to_vec())),
+                Box::new(FilterExpr::Port("8008".as_bytes().to_vec())),
+            )
+        );
+
+        assert_eq!(
+            parse_filter("src 10.0.0.1 or dst 10.0.0.2".as_bytes())
+                .unwrap()
+                .1,
+            FilterExpr::Or(
+                Box::new(FilterExpr::Src(Box::new(FilterExpr::Host(
+                    "10.0.0.1".as_bytes().to_vec()
+                )))),
+                Box::new(FilterExpr::Dst(Box::new(FilterExpr::Host(
+                    "10.0.0.2".as_bytes().to_vec()
+                )))),
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_rejects_trailing_garbage() {
+        // A typo'd combinator ("amd" instead of "and") must be a parse error, not a
+        // silent truncation to just the first term.
+        assert!(parse_filter("host 192.168.1.1 amd port 9999".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_filter_matches_src_qualifier() {
+        let expr = FilterExpr::Src(Box::new(FilterExpr::Host(
+            "192.168.0.10".as_bytes().to_vec(),
+        )));
+        let source = HostPort {
+            host: "192.168.0.10".as_bytes(),
+            port: "8008".as_bytes(),
+        };
+        let dest = HostPort {
+            host: "192.168.0.20".as_bytes(),
+            port: "50314".as_bytes(),
+        };
+
+        assert!(filter_matches(&expr, &source, &dest));
+        assert!(!filter_matches(&expr, &dest, &source));
+    }
+
+    #[test]
+    fn test_conn_key_canonicalizes_direction() {
+        let a = HostPort {
+            host: "192.168.0.10".as_bytes(),
+            port: "8008".as_bytes(),
+        };
+        let b = HostPort {
+            host: "192.168.0.20".as_bytes(),
+            port: "50314".as_bytes(),
+        };
+
+        assert_eq!(conn_key(&a, &b), conn_key(&b, &a));
+    }
+
+    #[test]
+    fn test_follow_out_files_data_by_direction() {
+        let mut follower = Follower::default();
+        let source = HostPort {
+            host: "192.168.0.10".as_bytes(),
+            port: "8008".as_bytes(),
+        };
+        let dest = HostPort {
+            host: "192.168.0.20".as_bytes(),
+            port: "50314".as_bytes(),
+        };
+
+        let repr = TcpRepr {
+            flags: TcpFlags::default(),
+            seq: Some(1),
+            ack: None,
+            win: 508,
+            options: None,
+        };
+        follow_out(&mut follower, &TcpdumpLine::Tcp(source, dest, repr), None);
+        follow_out(
+            &mut follower,
+            &TcpdumpLine::Data("4500 0233".as_bytes(), "".as_bytes()),
+            None,
+        );
+
+        let key = conn_key(
+            &HostPort {
+                host: "192.168.0.10".as_bytes(),
+                port: "8008".as_bytes(),
+            },
+            &HostPort {
+                host: "192.168.0.20".as_bytes(),
+                port: "50314".as_bytes(),
+            },
+        );
+        let connection = follower.connections.get(&key).unwrap();
+        assert_eq!(reassemble(&connection.forward), vec![0x45, 0x00, 0x02, 0x33]);
+        assert!(connection.backward.is_empty());
+    }
+
+    #[test]
+    fn test_follow_out_respects_filter() {
+        let mut follower = Follower::default();
+        let source = HostPort {
+            host: "192.168.0.10".as_bytes(),
+            port: "8008".as_bytes(),
+        };
+        let dest = HostPort {
+            host: "192.168.0.20".as_bytes(),
+            port: "50314".as_bytes(),
+        };
+
+        let repr = TcpRepr {
+            flags: TcpFlags::default(),
+            seq: Some(1),
+            ack: None,
+            win: 508,
+            options: None,
+        };
+        let (_, expr) = parse_filter("host 10.0.0.1".as_bytes()).unwrap();
+
+        follow_out(
+            &mut follower,
+            &TcpdumpLine::Tcp(source, dest, repr),
+            Some(&expr),
+        );
+        follow_out(
+            &mut follower,
+            &TcpdumpLine::Data("4500 0233".as_bytes(), "".as_bytes()),
+            Some(&expr),
+        );
+
+        assert!(follower.connections.is_empty());
+    }
 }